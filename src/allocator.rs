@@ -0,0 +1,63 @@
+use std::alloc::{Layout, self};
+use std::ptr::NonNull;
+
+/// The requested memory could not be allocated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocError;
+
+/// A source of raw memory that `RawVec` (and, through it, `Vec`) can draw
+/// its backing storage from. This mirrors the shape of the unstable
+/// `std::alloc::Allocator` trait, trimmed down to what this crate needs,
+/// so that custom arena or bump allocators can be plugged into `Vec`.
+pub trait Allocator {
+    /// Allocate a block of memory fitting `layout`.
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError>;
+
+    /// Grow a previously-allocated block from `old_layout` to
+    /// `new_layout`, preserving its contents.
+    ///
+    /// # Safety
+    /// `pointer` must have been allocated by this allocator using
+    /// `old_layout`, and `new_layout`'s size must be at least as large as
+    /// `old_layout`'s.
+    unsafe fn grow(
+        &self,
+        pointer: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<u8>, AllocError>;
+
+    /// Deallocate a block of memory previously allocated by this
+    /// allocator.
+    ///
+    /// # Safety
+    /// `pointer` must have been allocated by this allocator using
+    /// `layout`.
+    unsafe fn deallocate(&self, pointer: NonNull<u8>, layout: Layout);
+}
+
+/// The global heap, as provided by `std::alloc`. This is the allocator
+/// `Vec` uses unless told otherwise.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Global;
+
+impl Allocator for Global {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        let ptr = unsafe { alloc::alloc(layout) };
+        NonNull::new(ptr).ok_or(AllocError)
+    }
+
+    unsafe fn grow(
+        &self,
+        pointer: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<u8>, AllocError> {
+        let ptr = alloc::realloc(pointer.as_ptr(), old_layout, new_layout.size());
+        NonNull::new(ptr).ok_or(AllocError)
+    }
+
+    unsafe fn deallocate(&self, pointer: NonNull<u8>, layout: Layout) {
+        alloc::dealloc(pointer.as_ptr(), layout);
+    }
+}
@@ -0,0 +1,180 @@
+use std::alloc::Layout;
+use std::mem;
+use std::ptr::NonNull;
+
+use crate::allocator::{AllocError, Allocator, Global};
+
+/// The error returned when a fallible allocation (`Vec::try_reserve`)
+/// can't be satisfied. `Vec::try_push` reports failure by handing the
+/// element back instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryReserveError {
+    /// The requested capacity exceeds `isize::MAX` bytes, so no allocator
+    /// could ever satisfy it.
+    CapacityOverflow,
+    /// The allocator reported that it could not fulfill the request.
+    AllocError(AllocError),
+}
+
+/// Owns a heap allocation sized to hold `capacity` many `T`s, without
+/// tracking how many of them are actually initialized. `Vec<T, A>` builds
+/// its length bookkeeping on top of this, so all the `Layout` math, the
+/// `isize::MAX` overflow guard, and the zero-sized-type special-casing
+/// only need to live in one place.
+pub(crate) struct RawVec<T, A: Allocator = Global> {
+    pointer: NonNull<T>,
+    capacity: usize,
+    alloc: A,
+}
+
+impl<T, A: Allocator + Default> RawVec<T, A> {
+    /// Create a new `RawVec` with no backing allocation, using a
+    /// default-constructed allocator.
+    pub(crate) fn new() -> Self {
+        Self::new_in(A::default())
+    }
+}
+
+impl<T, A: Allocator> RawVec<T, A> {
+    /// Create a new `RawVec` with no backing allocation, drawing future
+    /// allocations from `alloc`.
+    pub(crate) fn new_in(alloc: A) -> Self {
+        // Zero-sized types never need to be allocated for, so we pretend
+        // we already have room for as many of them as anyone could ask
+        // for. This keeps `grow` from ever needing to run.
+        let capacity = if mem::size_of::<T>() == 0 { usize::MAX } else { 0 };
+        RawVec { pointer: NonNull::dangling(), capacity, alloc }
+    }
+
+    /// Create a `RawVec` with room for at least `capacity` many `T`s
+    /// already allocated from `alloc`.
+    pub(crate) fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+        let mut raw = Self::new_in(alloc);
+        if capacity != 0 {
+            // Route through the same `checked_mul`-guarded capacity math
+            // `try_reserve_to` uses, rather than a plain multiplication,
+            // so a huge caller-supplied `capacity` reports an overflow
+            // instead of silently wrapping and under-allocating.
+            raw.try_reserve_to(capacity).expect("Error allocating Vec");
+        }
+        raw
+    }
+
+    pub(crate) fn ptr(&self) -> NonNull<T> {
+        self.pointer
+    }
+
+    pub(crate) fn cap(&self) -> usize {
+        self.capacity
+    }
+
+    /// Resize the allocation. If it has no space allocated, it allocates
+    /// space for one element. If it has space allocated, it doubles the
+    /// amount of allocated space.
+    ///
+    /// Panics (or aborts, if the allocator itself aborts) on allocation
+    /// failure or capacity overflow; see `try_grow` for a fallible
+    /// version.
+    pub(crate) fn grow(&mut self) {
+        self.try_grow().expect("Out of memory in Vec reallocate");
+    }
+
+    /// The fallible core of `grow`: same behavior, but returns a
+    /// `TryReserveError` instead of panicking when the allocator can't
+    /// satisfy the request or the new capacity would overflow `isize`.
+    pub(crate) fn try_grow(&mut self) -> Result<(), TryReserveError> {
+        if mem::size_of::<T>() == 0 {
+            // `capacity` is already `usize::MAX` for ZSTs, so this should
+            // never actually be reached, but guard against it anyway
+            // rather than ever touching the allocator for a type with no
+            // size.
+            return Ok(());
+        }
+
+        // Need to manually specify the alignment and size allocated
+        let align = mem::align_of::<T>();
+        let elem_size = mem::size_of::<T>();
+
+        let (new_cap, result) = if self.capacity == 0 {
+            // The array was empty, so we make a new array of size 1
+            let layout = Layout::from_size_align(elem_size, align)
+                .map_err(|_| TryReserveError::CapacityOverflow)?;
+            (1, self.alloc.allocate(layout))
+        } else {
+            // Make a new array, and then copy it over
+            let new_cap = self.capacity * 2;
+            let old_num_bytes = self.capacity * elem_size;
+
+            // LLVM's GEP behaves poorly if you use an index greater than
+            // the max value in an isize.
+            // To accomplish this on a 64-bit architecture without ZSTs
+            // would require >8EB of memory (unlikely), or more if your
+            // type is > 1 byte in size, but this is preserved for 32-bit
+            // machines.
+            if old_num_bytes > (::std::isize::MAX as usize) / 2 {
+                return Err(TryReserveError::CapacityOverflow);
+            }
+
+            let old_layout = Layout::from_size_align(old_num_bytes, align)
+                .map_err(|_| TryReserveError::CapacityOverflow)?;
+            let new_layout = Layout::from_size_align(old_num_bytes * 2, align)
+                .map_err(|_| TryReserveError::CapacityOverflow)?;
+            // Here we actually reallocate the array
+            let result = unsafe { self.alloc.grow(self.pointer.cast(), old_layout, new_layout) };
+            (new_cap, result)
+        };
+
+        self.pointer = result.map_err(TryReserveError::AllocError)?.cast();
+        self.capacity = new_cap;
+        Ok(())
+    }
+
+    /// Ensure capacity for at least `target_cap` elements in a single
+    /// allocate-or-grow step, rather than `try_grow`'s one-doubling-at-a-
+    /// time approach. Used by `Vec::try_reserve`, which knows up front
+    /// how much room it actually needs.
+    pub(crate) fn try_reserve_to(&mut self, target_cap: usize) -> Result<(), TryReserveError> {
+        if mem::size_of::<T>() == 0 || self.capacity >= target_cap {
+            return Ok(());
+        }
+
+        let align = mem::align_of::<T>();
+        let elem_size = mem::size_of::<T>();
+        let new_num_bytes = elem_size.checked_mul(target_cap)
+            .filter(|&n| n <= ::std::isize::MAX as usize)
+            .ok_or(TryReserveError::CapacityOverflow)?;
+        let new_layout = Layout::from_size_align(new_num_bytes, align)
+            .map_err(|_| TryReserveError::CapacityOverflow)?;
+
+        let result = if self.capacity == 0 {
+            self.alloc.allocate(new_layout)
+        } else {
+            let old_num_bytes = elem_size * self.capacity;
+            let old_layout = Layout::from_size_align(old_num_bytes, align)
+                .map_err(|_| TryReserveError::CapacityOverflow)?;
+            unsafe { self.alloc.grow(self.pointer.cast(), old_layout, new_layout) }
+        };
+
+        self.pointer = result.map_err(TryReserveError::AllocError)?.cast();
+        self.capacity = target_cap;
+        Ok(())
+    }
+}
+
+impl<T, A: Allocator> Drop for RawVec<T, A> {
+    /// Deallocate the backing storage, if any was ever allocated.
+    fn drop(&mut self) {
+        // ZSTs are never actually allocated (their "capacity" is just a
+        // sentinel), so there's nothing to deallocate.
+        if self.capacity != 0 && mem::size_of::<T>() != 0 {
+            let align = mem::align_of::<T>();
+            let num_bytes = mem::size_of::<T>() * self.capacity;
+            unsafe {
+                self.alloc.deallocate(
+                    self.pointer.cast(),
+                    Layout::from_size_align(num_bytes, align)
+                        .expect("Unexpected panic while deallocating"));
+            }
+        }
+    }
+}
@@ -1,83 +1,83 @@
-use std::alloc::{Layout, self};
-use std::mem;
-use std::ops::{Deref, DerefMut};
-use std::ptr::{NonNull, self};
+use std::marker::PhantomData;
+use std::mem::{self, ManuallyDrop};
+use std::ops::{Bound, Deref, DerefMut, RangeBounds};
+use std::ptr::{self, NonNull};
+
+use crate::allocator::{Allocator, Global};
+use crate::raw_vec::{RawVec, TryReserveError};
 
 /// A re-implementation of the Vec class in the rust std. This is done
 /// purely for pedagogigal value, and is not something worth actually
 /// using.
-pub struct Vec<T> {
-    pointer: NonNull<T>,
-    capacity: usize,
+pub struct Vec<T, A: Allocator = Global> {
+    buf: RawVec<T, A>,
     length: usize,
 }
 
-impl<T> Vec<T> {
-    /// Create a new empty Vec
+impl<T> Vec<T, Global> {
+    /// Create a new empty Vec, backed by the global allocator.
     pub fn new() -> Self {
-        assert!(mem::size_of::<T>() != 0, "Zero-length types not yet implemented");
-        Vec { pointer: NonNull::dangling(), capacity: 0, length: 0 }
+        Vec { buf: RawVec::new(), length: 0 }
     }
 
-    /// Resize the Vec. If it has no space allocated, it allocates space
-    /// for one element. If it has space allocated, it doubles the
-    /// amount of allocated space.
-    fn grow(&mut self) {
-        unsafe {
-            // Need to manually specify the alignment and size allocated
-            let align = mem::align_of::<T>();
-            let elem_size = mem::size_of::<T>();
-
-            let (new_cap, ptr) = if self.capacity == 0 {
-                // The array was empty, so we make a new array of size 1
-                let ptr = alloc::alloc(Layout::from_size_align(elem_size, align)
-                                          .expect("Error allocating Vec"));
-                (1, ptr)
-            } else {
-                // Make a new array, and then copy it over
-                let new_cap = self.capacity * 2;
-                let old_num_bytes = self.capacity * elem_size;
-
-                // LLVM's GEP behaves poorly if you use an index greater
-                // than the max value in an isize.
-                // To accomplish this on a 64-bit architecture without
-                // ZSTs would require >8EB of memory (unlikely), or more
-                // if your type is > 1 byte in size, but this is
-                // preserved for 32-bit machines.
-                assert!(old_num_bytes <= (::std::isize::MAX as usize) / 2, "too many things");
-
-                let new_num_bytes = old_num_bytes * 2;
-                // Here we actually reallocate the array
-                let ptr = alloc::realloc(self.pointer.as_ptr() as *mut u8,
-                                            Layout::from_size_align(
-                                                old_num_bytes,
-                                                align
-                                            ).expect("Error re-allocating Vec"),
-                                            new_num_bytes);
-                (new_cap, ptr)
-            };
+    /// Create a new empty Vec, backed by the global allocator, with space
+    /// for at least `capacity` elements already allocated.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Vec { buf: RawVec::with_capacity_in(capacity, Global), length: 0 }
+    }
+}
 
-            // If the expect is hit, then we somehow ran out of memory.
-            // Given that the OS can use paging and will likely shut us
-            // down before we get to ridiculous amounts of memory, this
-            // probably means we requested far more space than exists in
-            // one go.
-            self.pointer = NonNull::new(ptr as *mut T).expect("Out of memory in Vec reallocate");
-            self.capacity = new_cap;
-        }
+impl<T, A: Allocator> Vec<T, A> {
+    /// Create a new empty Vec, drawing its backing storage from `alloc`.
+    pub fn new_in(alloc: A) -> Self {
+        Vec { buf: RawVec::new_in(alloc), length: 0 }
+    }
+
+    /// Create a new empty Vec, drawing its backing storage from `alloc`,
+    /// with space for at least `capacity` elements already allocated.
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+        Vec { buf: RawVec::with_capacity_in(capacity, alloc), length: 0 }
     }
 
     /// Append a value to the end of the Vec, reallocating if more space
     /// is necessary.
     /// Guaranteed to run in O(n) time, O(1) amortized
     pub fn push(&mut self, element: T) {
-        if self.length == self.capacity {
-            self.grow();
+        if self.try_push(element).is_err() {
+            panic!("Out of memory in Vec reallocate");
+        }
+    }
+
+    /// Ensures there is room for at least `additional` more elements
+    /// without reallocating, growing the backing allocation as needed in
+    /// a single step sized to fit the request. Unlike `push`'s implicit
+    /// growth, this reports allocation failure instead of panicking, for
+    /// callers that need to recover from running out of memory.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        let needed = self.length.checked_add(additional)
+            .ok_or(TryReserveError::CapacityOverflow)?;
+        if needed <= self.buf.cap() {
+            return Ok(());
+        }
+        // Still grow by at least a doubling, so a `try_reserve` for just
+        // one more element doesn't force a reallocation on every
+        // following push.
+        let target = needed.max(self.buf.cap().saturating_mul(2)).max(1);
+        self.buf.try_reserve_to(target)
+    }
+
+    /// Like `push`, but reports allocation failure instead of panicking,
+    /// handing the element back so the caller can decide what to do with
+    /// it.
+    pub fn try_push(&mut self, element: T) -> Result<(), T> {
+        if self.length == self.buf.cap() && self.buf.try_grow().is_err() {
+            return Err(element);
         }
         unsafe {
-            ptr::write(self.pointer.as_ptr().add(self.length), element);
+            ptr::write(self.buf.ptr().as_ptr().add(self.length), element);
         }
         self.length += 1;
+        Ok(())
     }
 
     /// Removes the last item from the Vec and returns it
@@ -87,7 +87,7 @@ impl<T> Vec<T> {
         } else {
             self.length -= 1;
             unsafe {
-                Some(ptr::read(self.pointer.as_ptr().add(self.length)))
+                Some(ptr::read(self.buf.ptr().as_ptr().add(self.length)))
             }
         }
     }
@@ -102,18 +102,18 @@ impl<T> Vec<T> {
     pub fn insert(&mut self, index: usize, element: T) {
         assert!(index <= self.length, "index out of bounds");
 
-        if self.length == self.capacity {
-            self.grow();
+        if self.length == self.buf.cap() {
+            self.buf.grow();
         }
 
         unsafe {
             if index < self.length {
                 ptr::copy(
-                    self.pointer.as_ptr().add(index),
-                    self.pointer.as_ptr().add(index+1),
+                    self.buf.ptr().as_ptr().add(index),
+                    self.buf.ptr().as_ptr().add(index+1),
                     self.length - index);
             }
-            ptr::write(self.pointer.as_ptr().add(index), element);
+            ptr::write(self.buf.ptr().as_ptr().add(index), element);
         }
         self.length += 1;
     }
@@ -124,9 +124,9 @@ impl<T> Vec<T> {
         assert!(index < self.length, "index out of bounds error");
         unsafe {
             self.length -= 1;
-            let result = ptr::read(self.pointer.as_ptr().add(index));
-            ptr::copy(self.pointer.as_ptr().add(index + 1),
-                      self.pointer.as_ptr().add(index),
+            let result = ptr::read(self.buf.ptr().as_ptr().add(index));
+            ptr::copy(self.buf.ptr().as_ptr().add(index + 1),
+                      self.buf.ptr().as_ptr().add(index),
                       self.length - index);
             result
         }
@@ -134,76 +134,138 @@ impl<T> Vec<T> {
 
     /// Consumes this Vec object and creates an IntoIter which iterates
     /// over the elements of this Vec
-    pub fn into_iter(self) -> IntoIter<T> {
-        let pointer = self.pointer;
-        let capacity = self.capacity;
+    pub fn into_iter(self) -> IntoIter<T, A> {
         let length = self.length;
+        unsafe {
+            let start = self.buf.ptr().as_ptr();
+            // For ZSTs, `.add(length)` lands on the same address as
+            // `start`, which would make a `start == end` emptiness check
+            // lie about how many elements are left. We track that
+            // count explicitly in `remaining` instead, so `end` only
+            // needs to be correct for the non-ZST case.
+            let end = if self.buf.cap() == 0 || mem::size_of::<T>() == 0 {
+                start
+            } else {
+                start.add(length)
+            };
+            // Move the buffer out without running Vec::drop, which would
+            // both drop our elements and deallocate out from under us.
+            let buf = ptr::read(&self.buf);
+            mem::forget(self);
+            IntoIter { buf, start, end, remaining: length }
+        }
+    }
+
+    /// Removes `range` from the Vec, returning an iterator over the
+    /// removed elements. If the `Drain` is dropped before it's been fully
+    /// consumed (whether or not it's iterated at all), the remaining
+    /// removed elements are dropped and the tail is shifted back to close
+    /// the gap, same as if the `Drain` had simply been iterated to
+    /// completion.
+    pub fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> Drain<'_, T, A> {
+        let length = self.length;
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n + 1,
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => length,
+        };
+        assert!(start <= end, "drain start is after end");
+        assert!(end <= length, "drain end is out of bounds");
 
-        mem::forget(self);
         unsafe {
-            IntoIter {
-                buffer: pointer,
-                capacity,
-                start: pointer.as_ptr(),
-                end: if capacity == 0 {
-                    pointer.as_ptr()
-                } else {
-                    pointer.as_ptr().add(length)
-                }
+            let base = self.buf.ptr().as_ptr();
+            // Pretend the drained range (and the tail after it) is gone
+            // until the Drain restores it, so a leaked Drain can't expose
+            // moved-from elements.
+            self.length = start;
+            Drain {
+                vec: NonNull::from(&mut *self),
+                start,
+                tail_ptr: base.add(end),
+                tail_len: length - end,
+                front: base.add(start),
+                back: base.add(end),
+                remaining: end - start,
+                _marker: PhantomData,
             }
         }
     }
+
+    /// Removes every element for which `pred` returns true, returning an
+    /// iterator that yields them by value and compacts the retained
+    /// elements in place as it goes. If the `ExtractIf` is dropped before
+    /// it's been fully consumed, it finishes scanning on the spot so the
+    /// Vec is left fully compacted either way.
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, T, F, A>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        let original_len = self.length;
+        // Elements get moved or dropped out from under us as we scan, so
+        // hide them from the Vec until the ExtractIf is done (or
+        // abandoned), the same way Drain does.
+        self.length = 0;
+        ExtractIf {
+            vec: NonNull::from(&mut *self),
+            original_len,
+            read: 0,
+            write: 0,
+            pred,
+            _marker: PhantomData,
+        }
+    }
 }
 
-impl<T> Drop for Vec<T> {
-    /// Drop all elements in the Vec and then deallocate resources,
-    /// because T may need to be dropped.
+impl<T, A: Allocator> Drop for Vec<T, A> {
+    /// Drop all elements in the Vec. The backing allocation, if any, is
+    /// freed afterwards when `buf` is dropped in turn.
     fn drop(&mut self) {
-        if self.capacity != 0 {
-            while let Some(_) = self.pop() {}
-            let align = mem::align_of::<T>();
-            let num_bytes = mem::size_of::<T>() * self.capacity;
-            unsafe {
-                alloc::dealloc(self.pointer.as_ptr() as *mut u8,
-                                Layout::from_size_align(
-                                    num_bytes,
-                                    align
-                                ).expect("Unexpected panic while deallocating"));
-            }
-        }
+        while let Some(_) = self.pop() {}
     }
 }
 
-impl<T> Deref for Vec<T> {
+impl<T, A: Allocator> Deref for Vec<T, A> {
     type Target = [T];
     fn deref(&self) -> &[T] {
         unsafe {
-            std::slice::from_raw_parts(self.pointer.as_ptr(), self.length)
+            std::slice::from_raw_parts(self.buf.ptr().as_ptr(), self.length)
         }
     }
 }
-impl<T> DerefMut for Vec<T> {
+impl<T, A: Allocator> DerefMut for Vec<T, A> {
     fn deref_mut(&mut self) -> &mut [T] {
         unsafe {
-            std::slice::from_raw_parts_mut(self.pointer.as_ptr(), self.length)
+            std::slice::from_raw_parts_mut(self.buf.ptr().as_ptr(), self.length)
         }
     }
 }
 
-pub struct IntoIter<T> {
-    buffer: NonNull<T>,
-    capacity: usize,
+pub struct IntoIter<T, A: Allocator = Global> {
+    // Never read directly; kept alive only so its `Drop` deallocates the
+    // backing buffer once iteration finishes.
+    #[allow(dead_code)]
+    buf: RawVec<T, A>,
     start: *const T,
     end: *const T,
+    // How many elements are left to yield. `end - start` can't be used
+    // for this when T is a ZST, since `start` and `end` are then the same
+    // dangling pointer regardless of how many elements remain.
+    remaining: usize,
 }
 
-impl<T> Iterator for IntoIter<T> {
+impl<T, A: Allocator> Iterator for IntoIter<T, A> {
     type Item = T;
 
     fn next(&mut self) -> Option<T> {
-        if self.start == self.end {
+        if self.remaining == 0 {
             None
         } else {
+            self.remaining -= 1;
             unsafe {
                 let result = ptr::read(self.start);
                 self.start = self.start.add(1);
@@ -213,15 +275,15 @@ impl<T> Iterator for IntoIter<T> {
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let length = (self.end as usize - self.start as usize) / mem::size_of::<T>();
-        (length, Some(length))
+        (self.remaining, Some(self.remaining))
     }
 }
-impl<T> DoubleEndedIterator for IntoIter<T> {
+impl<T, A: Allocator> DoubleEndedIterator for IntoIter<T, A> {
     fn next_back(&mut self) -> Option<T> {
-        if self.start == self.end {
+        if self.remaining == 0 {
             None
         } else {
+            self.remaining -= 1;
             unsafe {
                 self.end = self.end.sub(1);
                 Some(ptr::read(self.end))
@@ -230,22 +292,224 @@ impl<T> DoubleEndedIterator for IntoIter<T> {
     }
 }
 
-impl<T> Drop for IntoIter<T> {
+impl<T, A: Allocator> Drop for IntoIter<T, A> {
+    /// Drop any elements that haven't been yielded yet. `buf` is dropped
+    /// afterwards, deallocating the backing storage if any was ever
+    /// allocated.
     fn drop(&mut self) {
-        if self.capacity != 0 {
-            let align = mem::align_of::<T>();
-            let num_bytes = mem::size_of::<T>() * self.capacity;
+        while self.next().is_some() {}
+    }
+}
+
+/// An iterator over a removed range of elements from a `Vec`, created by
+/// `Vec::drain`. Dropping it (whether by running it to completion or by
+/// letting it go early) shifts whatever comes after the drained range
+/// back into place, closing the gap.
+pub struct Drain<'a, T, A: Allocator = Global> {
+    /// The source Vec, whose `length` we restore once draining is done.
+    vec: NonNull<Vec<T, A>>,
+    /// Index in the source buffer where the drained range (and so the
+    /// gap) starts.
+    start: usize,
+    /// Fixed pointer to the first element after the drained range, in
+    /// the source buffer.
+    tail_ptr: *const T,
+    /// How many elements come after the drained range.
+    tail_len: usize,
+    /// Next not-yet-yielded element from the front.
+    front: *const T,
+    /// One past the next not-yet-yielded element from the back.
+    back: *const T,
+    /// How many elements are left to yield.
+    remaining: usize,
+    _marker: PhantomData<&'a mut Vec<T, A>>,
+}
+
+impl<'a, T, A: Allocator> Drain<'a, T, A> {
+    /// Keep whatever elements this `Drain` hasn't yielded yet in the
+    /// source `Vec`, instead of dropping them along with the rest of the
+    /// drained range. Useful for a `Drain` that's been partially advanced
+    /// and then abandoned.
+    pub fn keep_rest(self) {
+        // Skip our own Drop impl: it would drop the unyielded elements,
+        // which is exactly what we're here to avoid.
+        let mut this = ManuallyDrop::new(self);
+        unsafe {
+            let vec = this.vec.as_mut();
+            let base = vec.buf.ptr().as_ptr();
+            let unyielded = this.remaining;
+
+            // Close the gap between the (now-removed) drained prefix and
+            // whatever we hadn't yielded yet.
+            if unyielded > 0 {
+                ptr::copy(this.front, base.add(this.start), unyielded);
+            }
+
+            // Then close the gap between that and the original tail.
+            let new_tail_start = this.start + unyielded;
+            if this.tail_len > 0 {
+                ptr::copy(this.tail_ptr, base.add(new_tail_start), this.tail_len);
+            }
+
+            vec.length = new_tail_start + this.tail_len;
+        }
+    }
+}
+
+impl<'a, T, A: Allocator> Iterator for Drain<'a, T, A> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.remaining == 0 {
+            None
+        } else {
+            self.remaining -= 1;
             unsafe {
-                while self.start != self.end {
-                    self.next();
+                let result = ptr::read(self.front);
+                self.front = self.front.add(1);
+                Some(result)
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T, A: Allocator> DoubleEndedIterator for Drain<'a, T, A> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.remaining == 0 {
+            None
+        } else {
+            self.remaining -= 1;
+            unsafe {
+                self.back = self.back.sub(1);
+                Some(ptr::read(self.back))
+            }
+        }
+    }
+}
+
+impl<'a, T, A: Allocator> Drop for Drain<'a, T, A> {
+    /// Drop any elements that haven't been yielded yet, then shift the
+    /// tail back to close the gap left by the drained range and restore
+    /// the source Vec's length.
+    ///
+    /// The tail restore happens through a guard constructed before we
+    /// drop the un-yielded elements, so if one of their `Drop` impls
+    /// panics, unwinding still runs the guard's `Drop` and the source
+    /// Vec ends up with its tail shifted back into place rather than
+    /// leaking it forever at the truncated length `Vec::drain` set up
+    /// front.
+    fn drop(&mut self) {
+        struct TailRestoreGuard<'b, 'a, T, A: Allocator>(&'b mut Drain<'a, T, A>);
+
+        impl<'b, 'a, T, A: Allocator> Drop for TailRestoreGuard<'b, 'a, T, A> {
+            fn drop(&mut self) {
+                let drain = &mut *self.0;
+                unsafe {
+                    let vec = drain.vec.as_mut();
+                    if drain.tail_len > 0 {
+                        let base = vec.buf.ptr().as_ptr();
+                        ptr::copy(drain.tail_ptr, base.add(drain.start), drain.tail_len);
+                    }
+                    vec.length = drain.start + drain.tail_len;
+                }
+            }
+        }
+
+        let guard = TailRestoreGuard(self);
+        while guard.0.next().is_some() {}
+    }
+}
+
+/// An iterator that removes the elements of a `Vec` matching a predicate,
+/// created by `Vec::extract_if`. Walks the Vec once, yielding each
+/// matching element by value and relocating the retained elements
+/// backward to close the gaps they leave behind. Dropping it before it's
+/// exhausted finishes the scan so the Vec ends up fully compacted either
+/// way.
+pub struct ExtractIf<'a, T, F, A: Allocator = Global>
+where
+    F: FnMut(&mut T) -> bool,
+{
+    vec: NonNull<Vec<T, A>>,
+    /// Length of the Vec when extraction began; everything up to here is
+    /// either retained-and-relocated or removed by the time we're done.
+    original_len: usize,
+    /// Index of the next element to scan.
+    read: usize,
+    /// Index where the next retained element should be relocated to.
+    write: usize,
+    pred: F,
+    _marker: PhantomData<&'a mut Vec<T, A>>,
+}
+
+impl<'a, T, F, A: Allocator> Iterator for ExtractIf<'a, T, F, A>
+where
+    F: FnMut(&mut T) -> bool,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        unsafe {
+            let vec = self.vec.as_mut();
+            let base = vec.buf.ptr().as_ptr();
+            while self.read < self.original_len {
+                let cursor = base.add(self.read);
+                let matched = (self.pred)(&mut *cursor);
+                self.read += 1;
+                if matched {
+                    return Some(ptr::read(cursor));
+                }
+                if self.write != self.read - 1 {
+                    ptr::copy_nonoverlapping(cursor, base.add(self.write), 1);
+                }
+                self.write += 1;
+            }
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.original_len - self.read))
+    }
+}
+
+impl<'a, T, F, A: Allocator> Drop for ExtractIf<'a, T, F, A>
+where
+    F: FnMut(&mut T) -> bool,
+{
+    /// Finish scanning so every surviving element gets relocated and
+    /// every matched element gets dropped, even if the caller stopped
+    /// iterating early, then restore the source Vec's length to the
+    /// compacted size.
+    ///
+    /// `write` is committed to the source Vec's length through a guard
+    /// constructed before we resume scanning, so if `pred` or a matched
+    /// element's `Drop` panics partway through, unwinding still commits
+    /// whatever progress `write` had already reached instead of leaving
+    /// the whole Vec at the `0`-length sentinel `extract_if` set up
+    /// front.
+    fn drop(&mut self) {
+        struct LenGuard<'b, 'a, T, F, A: Allocator>(&'b mut ExtractIf<'a, T, F, A>)
+        where
+            F: FnMut(&mut T) -> bool;
+
+        impl<'b, 'a, T, F, A: Allocator> Drop for LenGuard<'b, 'a, T, F, A>
+        where
+            F: FnMut(&mut T) -> bool,
+        {
+            fn drop(&mut self) {
+                unsafe {
+                    self.0.vec.as_mut().length = self.0.write;
                 }
-                alloc::dealloc(self.buffer.as_ptr() as *mut u8,
-                                Layout::from_size_align(
-                                    num_bytes,
-                                    align
-                                ).expect("Unexpected panic while deallocating"));
             }
         }
+
+        let guard = LenGuard(self);
+        for _ in &mut *guard.0 {}
     }
 }
 
@@ -356,4 +620,297 @@ mod tests {
         assert_eq!(iter.next(), None);
         assert_eq!(iter.next_back(), None);
     }
+
+    #[test]
+    pub fn test_zst() {
+        let mut v: Vec<()> = Vec::new();
+        v.push(());
+        v.push(());
+        v.push(());
+        assert_eq!(v.length(), 3);
+        assert_eq!(v.pop(), Some(()));
+        assert_eq!(v.length(), 2);
+
+        let mut iter = v.into_iter();
+        assert_eq!(iter.size_hint(), (2, Some(2)));
+        assert_eq!(iter.next(), Some(()));
+        assert_eq!(iter.next_back(), Some(()));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    pub fn test_with_capacity() {
+        let mut v: Vec<i64> = Vec::with_capacity(4);
+        assert_eq!(v.length(), 0);
+        v.push(1);
+        v.push(2);
+        assert_eq!(v.length(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error allocating Vec")]
+    pub fn test_with_capacity_overflow_panics() {
+        // `size_of::<u64>() * capacity` overflows `usize` here; this must
+        // report a capacity overflow rather than silently wrapping and
+        // allocating a buffer too small for the reported capacity.
+        let _: Vec<u64> = Vec::with_capacity(1usize << 61);
+    }
+
+    #[test]
+    pub fn test_new_in() {
+        use crate::allocator::Global;
+
+        let mut v: Vec<i64, Global> = Vec::new_in(Global);
+        v.push(1);
+        v.push(2);
+        assert_eq!(v.length(), 2);
+        assert_eq!(v.pop(), Some(2));
+    }
+
+    #[test]
+    pub fn test_try_reserve_and_try_push() {
+        let mut v: Vec<i64> = Vec::new();
+        assert!(v.try_reserve(4).is_ok());
+        assert!(v.length() == 0);
+        assert!(v.try_push(1).is_ok());
+        assert!(v.try_push(2).is_ok());
+        assert_eq!(v.pop(), Some(2));
+        assert_eq!(v.pop(), Some(1));
+    }
+
+    #[test]
+    pub fn test_try_push_reports_allocator_failure() {
+        use std::alloc::Layout;
+        use std::ptr::NonNull;
+
+        use crate::allocator::{AllocError, Allocator};
+
+        #[derive(Default)]
+        struct AlwaysFails;
+
+        impl Allocator for AlwaysFails {
+            fn allocate(&self, _layout: Layout) -> Result<NonNull<u8>, AllocError> {
+                Err(AllocError)
+            }
+
+            unsafe fn grow(
+                &self,
+                _pointer: NonNull<u8>,
+                _old_layout: Layout,
+                _new_layout: Layout,
+            ) -> Result<NonNull<u8>, AllocError> {
+                Err(AllocError)
+            }
+
+            unsafe fn deallocate(&self, _pointer: NonNull<u8>, _layout: Layout) {}
+        }
+
+        let mut v: Vec<i64, AlwaysFails> = Vec::new_in(AlwaysFails);
+        assert_eq!(v.try_push(1), Err(1));
+        assert_eq!(v.length(), 0);
+    }
+
+    #[test]
+    pub fn test_drain() {
+        let mut v: Vec<i64> = Vec::new();
+        for x in [1, 1, 2, 3, 5, 8, 13] {
+            v.push(x);
+        }
+        let drained: std::vec::Vec<i64> = v.drain(1..4).collect();
+        assert_eq!(drained, std::vec::Vec::from([1, 2, 3]));
+        assert_eq!(v.length(), 4);
+        assert_eq!(v[0], 1);
+        assert_eq!(v[1], 5);
+        assert_eq!(v[2], 8);
+        assert_eq!(v[3], 13);
+    }
+
+    #[test]
+    pub fn test_drain_partial_then_drop() {
+        let mut v: Vec<i64> = Vec::new();
+        for x in [1, 2, 3, 4, 5] {
+            v.push(x);
+        }
+        {
+            let mut drain = v.drain(1..4);
+            assert_eq!(drain.next(), Some(2));
+            // Dropping here without exhausting the iterator should still
+            // remove the rest of the drained range and restore the tail.
+        }
+        assert_eq!(v.length(), 2);
+        assert_eq!(v[0], 1);
+        assert_eq!(v[1], 5);
+    }
+
+    #[test]
+    pub fn test_drain_keep_rest() {
+        let mut v: Vec<i64> = Vec::new();
+        for x in [1, 2, 3, 4, 5] {
+            v.push(x);
+        }
+        {
+            let mut drain = v.drain(1..4);
+            assert_eq!(drain.next(), Some(2));
+            drain.keep_rest();
+        }
+        assert_eq!(v.length(), 4);
+        assert_eq!(v[0], 1);
+        assert_eq!(v[1], 3);
+        assert_eq!(v[2], 4);
+        assert_eq!(v[3], 5);
+    }
+
+    #[test]
+    pub fn test_drain_panic_safety() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        struct PanicOnDrop(i64);
+        impl Drop for PanicOnDrop {
+            fn drop(&mut self) {
+                if self.0 == 4 {
+                    panic!("boom");
+                }
+            }
+        }
+
+        let mut v: Vec<PanicOnDrop> = Vec::new();
+        for x in [1, 2, 3, 4, 5] {
+            v.push(PanicOnDrop(x));
+        }
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut drain = v.drain(1..4);
+            assert_eq!(drain.next().unwrap().0, 2);
+            // Dropping `drain` here drops the still-undrained elements
+            // (3 and 4); 4's Drop panics partway through.
+        }));
+        assert!(result.is_err());
+
+        // Even though the panic interrupted the drop, the tail (5) must
+        // have been shifted back and the Vec's length restored instead
+        // of staying stuck (and leaking 5) at the truncated length
+        // `drain` set up front.
+        assert_eq!(v.length(), 2);
+        assert_eq!(v[0].0, 1);
+        assert_eq!(v[1].0, 5);
+    }
+
+    #[test]
+    pub fn test_extract_if() {
+        let mut v: Vec<i64> = Vec::new();
+        for x in 1..=10 {
+            v.push(x);
+        }
+        let evens: std::vec::Vec<i64> = v.extract_if(|x| *x % 2 == 0).collect();
+        assert_eq!(evens, std::vec::Vec::from([2, 4, 6, 8, 10]));
+        assert_eq!(v.length(), 5);
+        for (i, expected) in [1, 3, 5, 7, 9].into_iter().enumerate() {
+            assert_eq!(v[i], expected);
+        }
+    }
+
+    #[test]
+    pub fn test_extract_if_dropped_early() {
+        let mut v: Vec<i64> = Vec::new();
+        for x in 1..=6 {
+            v.push(x);
+        }
+        {
+            let mut extracted = v.extract_if(|x| *x % 2 == 0);
+            assert_eq!(extracted.next(), Some(2));
+            // Dropping here without exhausting the iterator should still
+            // finish removing the rest of the matches and compact the
+            // survivors.
+        }
+        assert_eq!(v.length(), 3);
+        assert_eq!(v[0], 1);
+        assert_eq!(v[1], 3);
+        assert_eq!(v[2], 5);
+    }
+
+    #[test]
+    pub fn test_extract_if_panic_safety() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        struct PanicOnDrop(i64);
+        impl Drop for PanicOnDrop {
+            fn drop(&mut self) {
+                if self.0 == 4 {
+                    panic!("boom");
+                }
+            }
+        }
+
+        let mut v: Vec<PanicOnDrop> = Vec::new();
+        for x in 1..=6 {
+            v.push(PanicOnDrop(x));
+        }
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut extracted = v.extract_if(|x| x.0 % 2 == 0);
+            assert_eq!(extracted.next().unwrap().0, 2);
+            // Dropping `extracted` here resumes the scan: 3 is retained
+            // and relocated, then 4 is matched and dropped immediately,
+            // which panics.
+        }));
+        assert!(result.is_err());
+
+        // Everything compacted before the panic (1, then 3 relocated
+        // behind it) must still be reachable through the Vec's length,
+        // rather than the whole Vec being leaked at the `0`-length
+        // sentinel `extract_if` set up front.
+        assert_eq!(v.length(), 2);
+        assert_eq!(v[0].0, 1);
+        assert_eq!(v[1].0, 3);
+    }
+
+    #[test]
+    pub fn test_try_reserve_single_allocation() {
+        use std::alloc::Layout;
+        use std::cell::Cell;
+        use std::ptr::NonNull;
+        use std::rc::Rc;
+
+        use crate::allocator::{AllocError, Allocator, Global};
+
+        struct CountingAllocator {
+            calls: Rc<Cell<usize>>,
+        }
+
+        impl Allocator for CountingAllocator {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+                self.calls.set(self.calls.get() + 1);
+                Global.allocate(layout)
+            }
+
+            unsafe fn grow(
+                &self,
+                pointer: NonNull<u8>,
+                old_layout: Layout,
+                new_layout: Layout,
+            ) -> Result<NonNull<u8>, AllocError> {
+                self.calls.set(self.calls.get() + 1);
+                Global.grow(pointer, old_layout, new_layout)
+            }
+
+            unsafe fn deallocate(&self, pointer: NonNull<u8>, layout: Layout) {
+                Global.deallocate(pointer, layout)
+            }
+        }
+
+        let calls = Rc::new(Cell::new(0));
+        let mut v: Vec<i64, CountingAllocator> =
+            Vec::new_in(CountingAllocator { calls: calls.clone() });
+
+        assert!(v.try_reserve(1000).is_ok());
+        // A single allocate call for the whole request, not a series of
+        // one-doubling-at-a-time reallocations.
+        assert_eq!(calls.get(), 1);
+
+        for x in 0..1000 {
+            assert!(v.try_push(x).is_ok());
+        }
+        // Capacity from the single try_reserve already covers every push.
+        assert_eq!(calls.get(), 1);
+    }
 }